@@ -0,0 +1,122 @@
+//! Async framed transport for native messaging, built on `tokio_util`'s
+//! `Decoder`/`Encoder` traits.
+//!
+//! This lets a host drive native messaging through `FramedRead`/`FramedWrite`
+//! over any `AsyncRead`/`AsyncWrite`, instead of blocking a whole thread per
+//! message the way `read_input`/`send_message` do. Only compiled when the
+//! `async` feature is enabled.
+
+use crate::{Error, DEFAULT_MAX_MESSAGE_SIZE};
+use bytes::{Buf, BufMut, BytesMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::marker::PhantomData;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Where `NativeMessagingCodec::decode` is up to: waiting for the 4-byte
+/// length header, or waiting for `len` more bytes of JSON body.
+enum DecodeState {
+    ReadHeader,
+    ReadBody { len: usize },
+}
+
+/// A `Decoder`/`Encoder` pair that frames messages the same way
+/// `read_input`/`send_message` do. Defaults to decoding into
+/// `serde_json::Value`; pick a concrete `T: DeserializeOwned` to get
+/// typed messages instead, mirroring `read_message`.
+pub struct NativeMessagingCodec<T = Value> {
+    state: DecodeState,
+    max_size: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T> NativeMessagingCodec<T> {
+    /// Creates a codec that rejects inbound messages over
+    /// `DEFAULT_MAX_MESSAGE_SIZE`, matching `read_message`'s default.
+    pub fn new() -> Self {
+        NativeMessagingCodec {
+            state: DecodeState::ReadHeader,
+            max_size: DEFAULT_MAX_MESSAGE_SIZE,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a codec that rejects inbound messages over `max_size`,
+    /// mirroring `read_message_with_limit`.
+    pub fn with_max_size(max_size: u32) -> Self {
+        NativeMessagingCodec {
+            state: DecodeState::ReadHeader,
+            max_size,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for NativeMessagingCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for NativeMessagingCodec<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<T>, Error> {
+        loop {
+            match self.state {
+                DecodeState::ReadHeader => {
+                    if buf.len() < 4 {
+                        return Ok(None);
+                    }
+                    let mut len_bytes = [0u8; 4];
+                    len_bytes.copy_from_slice(&buf[..4]);
+                    let len = u32::from_ne_bytes(len_bytes);
+                    if len > self.max_size {
+                        return Err(Error::MessageTooLargeRead {
+                            size: len as usize,
+                            limit: self.max_size as usize,
+                        });
+                    }
+                    buf.advance(4);
+                    buf.reserve(len as usize);
+                    self.state = DecodeState::ReadBody { len: len as usize };
+                }
+                DecodeState::ReadBody { len } => {
+                    if buf.len() < len {
+                        return Ok(None);
+                    }
+                    let body = buf.split_to(len);
+                    // Reset before deserializing: the body bytes are already
+                    // gone from `buf` at this point, so a parse error must
+                    // not leave us stuck waiting for a body that no longer
+                    // exists, which would desync every frame after it.
+                    self.state = DecodeState::ReadHeader;
+                    let value = serde_json::from_slice(&body)?;
+                    return Ok(Some(value));
+                }
+            }
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<&T> for NativeMessagingCodec<T> {
+    type Error = Error;
+
+    fn encode(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), Error> {
+        let msg = serde_json::to_string(item)?;
+        let len = msg.len();
+        // Chrome won't accept a message larger than 1MB. Compare as
+        // `usize` before casting down to `u32`, so a length that would
+        // wrap past `u32::MAX` is rejected instead of truncating into a
+        // length header that no longer matches the bytes written below.
+        if len > 1024 * 1024 {
+            return Err(Error::MessageTooLarge { size: len });
+        }
+        dst.reserve(4 + len);
+        dst.put_slice(&(len as u32).to_ne_bytes());
+        dst.put_slice(msg.as_bytes());
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ pub enum Error {
     Io(io::Error),
     Serde(serde_json::Error),
     MessageTooLarge { size: usize },
+    MessageTooLargeRead { size: usize, limit: usize },
     NoMoreInput,
 }
 
@@ -34,6 +35,10 @@ impl fmt::Display for Error {
             Error::MessageTooLarge { size } => {
                 f.write_fmt(format_args!("message too large: {:?} bytes", size))
             }
+            Error::MessageTooLargeRead { size, limit } => f.write_fmt(format_args!(
+                "incoming message too large: {:?} bytes, limit is {:?} bytes",
+                size, limit
+            )),
             Error::NoMoreInput => f.write_str("the input stream reached the end"),
         }
     }
@@ -1,11 +1,15 @@
 mod errors;
+#[cfg(feature = "async")]
+pub mod codec;
 
 pub use crate::errors::Error;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::{json, Value};
 use std::fmt::Display;
 use std::io;
 use std::io::{Read, Write};
+use std::ops::ControlFlow;
 use std::panic;
 
 /// Writes the given JSON data to stdout, thereby 'sending' a message
@@ -20,8 +24,24 @@ use std::panic;
 ///
 /// send!({ "msg": "Hello, world!" });
 /// ```
+///
+/// A `Messenger` can be targeted with a leading `via` instead of sending
+/// to stdout directly:
+///
+/// ```
+/// use chrome_native_messaging::{send, Messenger};
+/// use serde_json::json;
+/// use std::io;
+///
+/// let mut messenger = Messenger::new(io::empty(), io::sink());
+/// send!(via messenger, { "msg": "Hello, world!" }).unwrap();
+/// ```
 #[macro_export]
 macro_rules! send {
+    (via $messenger:expr, $($json:tt)+) => {{
+        let v = json!($($json),+);
+        $messenger.send(&v)
+    }};
     ($($json:tt)+) => {{
         let v = json!($($json),+);
         $crate::send_message(::std::io::stdout(), &v)
@@ -44,11 +64,127 @@ macro_rules! send {
 /// read_input(io::stdin())
 ///     .err().expect("doctest should return unexpected eof");
 ///
-pub fn read_input<R: Read>(mut input: R) -> Result<Value, Error> {
+pub fn read_input<R: Read>(input: R) -> Result<Value, Error> {
+    read_message(input)
+}
+
+/// Byte order used to encode/decode the 4-byte length header that
+/// precedes every native messaging frame.
+///
+/// Talking to Chrome directly always uses `Native`, since both ends run
+/// on the same machine. Pick `Little` or `Big` when messages are
+/// captured, replayed, or proxied across architectures, or when a test
+/// asserts an exact byte layout and needs it to be portable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    /// The host machine's native byte order. Matches Chrome's own
+    /// behaviour and is the default used by `read_input`/`send_message`.
+    Native,
+    Little,
+    Big,
+}
+
+impl Endianness {
+    fn read_u32(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endianness::Native => u32::from_ne_bytes(bytes),
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn write_u32(self, value: u32) -> [u8; 4] {
+        match self {
+            Endianness::Native => value.to_ne_bytes(),
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+/// The largest inbound message `read_input`/`read_message` will accept
+/// without an explicit limit, matching Chrome's documented 1 MiB receive
+/// limit for messages sent *to* a native messaging host.
+/// (https://developer.chrome.com/extensions/nativeMessaging#native-messaging-host-protocol)
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 1024 * 1024;
+
+/// Reads input from a stream, decoded according to
+/// Chrome's own documentation on native messaging, and deserializes
+/// the framed body directly into `T`.
+/// (https://developer.chrome.com/extensions/nativeMessaging)
+///
+/// 1. A 32bit unsigned integer specifies how long the message is.
+/// 2. The message is encoded in JSON
+///
+/// Any malformed input is reported through `Error::Serde`, so callers
+/// don't need to re-walk a `Value` to convert it to their own type.
+/// The incoming length header is checked against
+/// `DEFAULT_MAX_MESSAGE_SIZE` before anything is allocated; use
+/// `read_message_with_limit` to configure a different bound.
+///
+/// # Example
+///
+/// ```
+/// use std::io;
+/// use chrome_native_messaging::{read_message, Error};
+///
+/// read_message::<String, _>(io::stdin())
+///     .err().expect("doctest should return unexpected eof");
+///
+pub fn read_message<T: DeserializeOwned, R: Read>(input: R) -> Result<T, Error> {
+    read_message_with_limit(input, DEFAULT_MAX_MESSAGE_SIZE)
+}
+
+/// Like [`read_input`], but the incoming length header is checked
+/// against `limit` instead of `DEFAULT_MAX_MESSAGE_SIZE` before the
+/// receive buffer is allocated. Use this when talking to an untrusted
+/// renderer that shouldn't be able to make the host allocate up to 4 GiB
+/// from a forged header.
+pub fn read_input_with_limit<R: Read>(input: R, limit: u32) -> Result<Value, Error> {
+    read_message_with_limit(input, limit)
+}
+
+/// Like [`read_message`], but the incoming length header is checked
+/// against `limit` instead of `DEFAULT_MAX_MESSAGE_SIZE` before the
+/// receive buffer is allocated.
+pub fn read_message_with_limit<T: DeserializeOwned, R: Read>(
+    input: R,
+    limit: u32,
+) -> Result<T, Error> {
+    read_message_with(input, limit, Endianness::Native)
+}
+
+/// Like [`read_input`], but the length header is decoded using
+/// `endianness` instead of the host's native byte order. Use this to
+/// read frames captured or forwarded from another architecture.
+pub fn read_input_with<R: Read>(input: R, endianness: Endianness) -> Result<Value, Error> {
+    read_message_with(input, DEFAULT_MAX_MESSAGE_SIZE, endianness)
+}
+
+/// Like [`read_message`], but the length header is decoded using
+/// `endianness` instead of the host's native byte order, and checked
+/// against `limit` before the receive buffer is allocated.
+pub fn read_message_with<T: DeserializeOwned, R: Read>(
+    mut input: R,
+    limit: u32,
+    endianness: Endianness,
+) -> Result<T, Error> {
     let mut buf = [0; 4];
-    match input.read_exact(&mut buf).map(|()| u32::from_ne_bytes(buf)) {
+    match input.read_exact(&mut buf).map(|()| endianness.read_u32(buf)) {
         Ok(length) => {
             //println!("Found length: {}", length);
+            if length > limit {
+                // Drain the declared body in bounded chunks rather than
+                // just bailing: callers of this guard are long-running
+                // loops over a persistent stream (`event_loop`, `Messenger`),
+                // and leaving the body unread would desync every message
+                // that follows.
+                io::copy(&mut (&mut input).take(u64::from(length)), &mut io::sink())?;
+                return Err(Error::MessageTooLargeRead {
+                    size: length as usize,
+                    limit: limit as usize,
+                });
+            }
             let mut buffer = vec![0; length as usize];
             input.read_exact(&mut buffer)?;
             let value = serde_json::from_slice(&buffer)?;
@@ -82,7 +218,29 @@ pub fn read_input<R: Read>(mut input: R) -> Result<Value, Error> {
 /// send_message(io::stdout(), &BasicMessage { payload: "Hello, World! "})
 ///     .expect("failed to send to stdout");
 /// ```
-pub fn send_message<W: Write, T: Serialize>(mut output: W, value: &T) -> Result<(), Error> {
+pub fn send_message<W: Write, T: Serialize>(output: W, value: &T) -> Result<(), Error> {
+    send_message_with(output, value, Endianness::Native)
+}
+
+/// Like [`send_message`], but the length header is encoded using
+/// `endianness` instead of the host's native byte order. Use this to
+/// produce frames a peer on another architecture (or a golden-file
+/// test) can decode unambiguously.
+///
+/// # Example
+///
+/// ```
+/// use chrome_native_messaging::{send_message_with, Endianness};
+/// use std::io;
+///
+/// send_message_with(io::sink(), &"hello", Endianness::Big)
+///     .expect("failed to send");
+/// ```
+pub fn send_message_with<W: Write, T: Serialize>(
+    mut output: W,
+    value: &T,
+    endianness: Endianness,
+) -> Result<(), Error> {
     let msg = serde_json::to_string(value)?;
     let len = msg.len();
     // Chrome won't accept a message larger than 1MB
@@ -90,7 +248,7 @@ pub fn send_message<W: Write, T: Serialize>(mut output: W, value: &T) -> Result<
         return Err(Error::MessageTooLarge { size: len });
     }
     let len = len as u32; // Cast is safe due to size check above
-    let len_bytes = len.to_ne_bytes();
+    let len_bytes = endianness.write_u32(len);
     output.write_all(&len_bytes)?;
     output.write_all(msg.as_bytes())?;
     output.flush()?;
@@ -166,3 +324,202 @@ where
         }
     }
 }
+
+/// Starts an 'event loop' which listens and writes to
+/// stdin and stdout respectively, just like [`event_loop`], except the
+/// callback receives the request already deserialized into `T` instead
+/// of a raw `Value`.
+///
+/// # Example
+///
+/// ```
+/// use chrome_native_messaging::event_loop_typed;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize)]
+/// struct Request {
+///     name: String,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct BasicMessage<'a> {
+///     payload: &'a str
+/// }
+///
+/// event_loop_typed(|req: Request| match req.name.as_str() {
+///     "" => Err("empty name"),
+///     _ => Ok(BasicMessage { payload: "Hello, World!" })
+/// });
+///
+/// ```
+pub fn event_loop_typed<T, U, E, F>(callback: F)
+where
+    F: Fn(T) -> Result<U, E>,
+    T: DeserializeOwned,
+    U: Serialize,
+    E: Display,
+{
+    panic::set_hook(Box::new(handle_panic));
+
+    loop {
+        // wait for input
+        match read_message::<T, _>(io::stdin()) {
+            Ok(v) => match callback(v) {
+                Ok(response) => send_message(io::stdout(), &response).unwrap(),
+                Err(e) => send!({ "error": format!("{}", e) }).unwrap(),
+            },
+            Err(e) => {
+                // if the input stream has finished, then we exit the event loop
+                if let Error::NoMoreInput = e {
+                    break;
+                }
+                send!({ "error": format!("{}", e) }).unwrap();
+            }
+        }
+    }
+}
+
+/// Returns `true` if `err` wraps an `io::Error` of kind `BrokenPipe`,
+/// i.e. the other end of the pipe (typically Chrome) has gone away.
+fn is_broken_pipe(err: &Error) -> bool {
+    matches!(err, Error::Io(io_err) if io_err.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Runs an event loop over arbitrary `input`/`output` streams, like
+/// [`event_loop`]/[`event_loop_typed`], but instead of panicking on a
+/// failed `send_message` it hands the error to `on_error` and lets the
+/// caller decide whether to keep going (`ControlFlow::Continue`) or stop
+/// the loop (`ControlFlow::Break`).
+///
+/// A write failure caused by a broken pipe (stdout closed, Chrome gone)
+/// is treated the same as `Error::NoMoreInput`: a clean exit that never
+/// reaches `on_error`. This makes the loop safe to use in long-running
+/// hosts, where a panic would otherwise re-enter `handle_panic` and try
+/// to write to the same broken stream again.
+///
+/// # Example
+///
+/// ```no_run
+/// use chrome_native_messaging::event_loop_with;
+/// use serde_json::Value;
+/// use std::io;
+/// use std::ops::ControlFlow;
+///
+/// event_loop_with(
+///     io::stdin(),
+///     io::stdout(),
+///     |value: Value| -> Result<Value, &'static str> { Ok(value) },
+///     |err| {
+///         eprintln!("native messaging error: {}", err);
+///         ControlFlow::Continue(())
+///     },
+/// )
+/// .unwrap();
+/// ```
+pub fn event_loop_with<T, U, E, F, R, W, H>(
+    mut input: R,
+    mut output: W,
+    callback: F,
+    mut on_error: H,
+) -> Result<(), Error>
+where
+    F: Fn(T) -> Result<U, E>,
+    T: DeserializeOwned,
+    U: Serialize,
+    E: Display,
+    R: Read,
+    W: Write,
+    H: FnMut(Error) -> ControlFlow<()>,
+{
+    loop {
+        match read_message::<T, _>(&mut input) {
+            Ok(v) => {
+                let send_result = match callback(v) {
+                    Ok(response) => send_message(&mut output, &response),
+                    Err(e) => send_message(&mut output, &json!({ "error": format!("{}", e) })),
+                };
+                if let Err(e) = send_result {
+                    if is_broken_pipe(&e) {
+                        return Ok(());
+                    }
+                    if let ControlFlow::Break(()) = on_error(e) {
+                        return Ok(());
+                    }
+                }
+            }
+            // if the input stream has finished, then we exit the event loop
+            Err(Error::NoMoreInput) => return Ok(()),
+            Err(e) => {
+                if let ControlFlow::Break(()) = on_error(e) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Owns a pair of read/write halves and speaks native messaging over
+/// them, without hardwiring `io::stdin()`/`io::stdout()` the way
+/// `event_loop` does. This makes it possible to drive native messaging
+/// over an in-memory pipe in tests, a Unix socket relay, or any other
+/// `Read`/`Write` pair.
+pub struct Messenger<R: Read, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: Read, W: Write> Messenger<R, W> {
+    /// Wraps an existing read/write pair.
+    pub fn new(input: R, output: W) -> Self {
+        Messenger { input, output }
+    }
+
+    /// Reads the next incoming message as a `Value`, like `read_input`.
+    pub fn recv(&mut self) -> Result<Value, Error> {
+        read_input(&mut self.input)
+    }
+
+    /// Reads the next incoming message, deserialized directly into `T`,
+    /// like `read_message`.
+    pub fn recv_message<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
+        read_message(&mut self.input)
+    }
+
+    /// Sends `value` as the next outgoing message, like `send_message`.
+    pub fn send<T: Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        send_message(&mut self.output, value)
+    }
+
+    /// Runs an event loop over this messenger's streams, like
+    /// `event_loop_typed`: a bad message (malformed JSON, etc.) is
+    /// reported back through `send` and the loop keeps going, just like
+    /// `event_loop`/`event_loop_typed` do. The loop
+    /// only stops once the input is exhausted (`Error::NoMoreInput`), or
+    /// a `send` call itself fails.
+    pub fn run<T, U, E, F>(&mut self, callback: F) -> Result<(), Error>
+    where
+        F: Fn(T) -> Result<U, E>,
+        T: DeserializeOwned,
+        U: Serialize,
+        E: Display,
+    {
+        loop {
+            match self.recv_message::<T>() {
+                Ok(v) => match callback(v) {
+                    Ok(response) => self.send(&response)?,
+                    Err(e) => self.send(&json!({ "error": format!("{}", e) }))?,
+                },
+                Err(Error::NoMoreInput) => return Ok(()),
+                Err(e) => self.send(&json!({ "error": format!("{}", e) }))?,
+            }
+        }
+    }
+}
+
+impl Messenger<io::Stdin, io::Stdout> {
+    /// Creates a `Messenger` over the process's stdin/stdout, the
+    /// default transport used by `event_loop`.
+    pub fn stdio() -> Self {
+        Messenger::new(io::stdin(), io::stdout())
+    }
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "async")]
+
+use bytes::BytesMut;
+use chrome_native_messaging::codec::NativeMessagingCodec;
+use serde_json::{json, Value};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn test_round_trip() {
+    let mut codec = NativeMessagingCodec::<Value>::new();
+    let mut buf = BytesMut::new();
+
+    let message = json!({ "msg": "Hello, world!" });
+    codec.encode(&message, &mut buf).unwrap();
+
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(message));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+}
+
+#[test]
+fn test_malformed_frame_does_not_desync_the_stream() {
+    let mut codec = NativeMessagingCodec::<Value>::new();
+    let mut buf = BytesMut::new();
+
+    // A well-formed header followed by a body that isn't valid JSON.
+    let bad_body = b"not json";
+    buf.extend_from_slice(&(bad_body.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(bad_body);
+
+    assert!(codec.decode(&mut buf).is_err());
+
+    // A subsequent, perfectly valid frame must still decode correctly,
+    // rather than being misread as the stale body of the failed frame.
+    let good_message = json!({ "ok": true });
+    codec.encode(&good_message, &mut buf).unwrap();
+
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(good_message));
+}
@@ -0,0 +1,30 @@
+use chrome_native_messaging::{read_message_with, send_message_with, Endianness};
+use serde_json::{json, Value};
+
+#[test]
+fn test_send_message_with_pins_exact_byte_layout() {
+    let message = json!({ "msg": "hi" });
+    let body = serde_json::to_vec(&message).unwrap();
+
+    let mut little = Vec::new();
+    send_message_with(&mut little, &message, Endianness::Little).unwrap();
+    assert_eq!(&little[..4], &(body.len() as u32).to_le_bytes());
+    assert_eq!(&little[4..], &body[..]);
+
+    let mut big = Vec::new();
+    send_message_with(&mut big, &message, Endianness::Big).unwrap();
+    assert_eq!(&big[..4], &(body.len() as u32).to_be_bytes());
+    assert_eq!(&big[4..], &body[..]);
+}
+
+#[test]
+fn test_read_message_with_round_trips_non_native_endianness() {
+    let message = json!({ "msg": "hi" });
+
+    let mut buf = Vec::new();
+    send_message_with(&mut buf, &message, Endianness::Big).unwrap();
+
+    let decoded: Value =
+        read_message_with(buf.as_slice(), 1024 * 1024, Endianness::Big).unwrap();
+    assert_eq!(decoded, message);
+}
@@ -0,0 +1,88 @@
+use chrome_native_messaging::{event_loop_with, send_message};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::io::{self, Cursor, Write};
+use std::ops::ControlFlow;
+
+struct BrokenPipeWriter;
+
+impl Write for BrokenPipeWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "pipe closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn frame(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    send_message(&mut buf, value).unwrap();
+    buf
+}
+
+#[test]
+fn test_broken_pipe_write_is_a_clean_exit() {
+    let input = frame(&json!({ "msg": "hi" }));
+    let on_error_called = RefCell::new(false);
+
+    let result = event_loop_with(
+        Cursor::new(input),
+        BrokenPipeWriter,
+        |value: Value| -> Result<Value, &'static str> { Ok(value) },
+        |_err| {
+            *on_error_called.borrow_mut() = true;
+            ControlFlow::Continue(())
+        },
+    );
+
+    assert!(result.is_ok());
+    assert!(
+        !*on_error_called.borrow(),
+        "a broken pipe write failure should exit cleanly without reaching on_error"
+    );
+}
+
+#[test]
+fn test_on_error_break_stops_the_loop() {
+    // A header claiming a 4-byte body that never arrives: the header read
+    // succeeds, but the body read hits EOF, producing a non-fatal `Error`
+    // (not `NoMoreInput`, which only comes from an EOF on the header itself).
+    let input = 4u32.to_ne_bytes().to_vec();
+    let calls = RefCell::new(0);
+
+    let result = event_loop_with(
+        Cursor::new(input),
+        io::sink(),
+        |value: Value| -> Result<Value, &'static str> { Ok(value) },
+        |_err| {
+            *calls.borrow_mut() += 1;
+            ControlFlow::Break(())
+        },
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(*calls.borrow(), 1);
+}
+
+#[test]
+fn test_on_error_continue_keeps_going_until_no_more_input() {
+    let input = 4u32.to_ne_bytes().to_vec();
+    let calls = RefCell::new(0);
+
+    let result = event_loop_with(
+        Cursor::new(input),
+        io::sink(),
+        |value: Value| -> Result<Value, &'static str> { Ok(value) },
+        |_err| {
+            *calls.borrow_mut() += 1;
+            ControlFlow::Continue(())
+        },
+    );
+
+    // The dangling-header error fires on_error once; the next read hits a
+    // clean `NoMoreInput` and the loop exits without calling on_error again.
+    assert!(result.is_ok());
+    assert_eq!(*calls.borrow(), 1);
+}
@@ -0,0 +1,76 @@
+use chrome_native_messaging::{read_input, send_message, Messenger};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+
+#[derive(Clone)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuf {
+    fn new() -> Self {
+        SharedBuf(Rc::new(RefCell::new(Vec::new())))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+}
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn frame(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    send_message(&mut buf, value).unwrap();
+    buf
+}
+
+#[test]
+fn test_recv_and_send_round_trip() {
+    let input = frame(&json!({ "msg": "hi" }));
+    let output = SharedBuf::new();
+    let mut messenger = Messenger::new(Cursor::new(input), output.clone());
+
+    assert_eq!(messenger.recv().unwrap(), json!({ "msg": "hi" }));
+
+    messenger.send(&json!({ "reply": "ok" })).unwrap();
+
+    let sent: Value = read_input(output.bytes().as_slice()).unwrap();
+    assert_eq!(sent, json!({ "reply": "ok" }));
+}
+
+#[test]
+fn test_run_reports_bad_messages_and_keeps_going() {
+    let mut input = Vec::new();
+    input.extend(frame(&json!("bad")));
+    input.extend(frame(&json!("good")));
+
+    let output = SharedBuf::new();
+    let mut messenger = Messenger::new(Cursor::new(input), output.clone());
+
+    messenger
+        .run(|v: Value| -> Result<Value, &'static str> {
+            match v.as_str() {
+                Some("bad") => Err("bad input"),
+                _ => Ok(json!({ "got": v })),
+            }
+        })
+        .unwrap();
+
+    let sent = output.bytes();
+    let mut reader = sent.as_slice();
+
+    let first: Value = read_input(&mut reader).unwrap();
+    assert_eq!(first, json!({ "error": "bad input" }));
+
+    let second: Value = read_input(&mut reader).unwrap();
+    assert_eq!(second, json!({ "got": "good" }));
+}
@@ -1,7 +1,7 @@
-use chrome_native_messaging::{send_message, Error};
+use chrome_native_messaging::{read_input_with_limit, send_message, Error};
 use serde::Serialize;
-use serde_json::json;
-use std::io::sink;
+use serde_json::{json, Value};
+use std::io::{sink, Cursor};
 
 #[derive(Serialize)]
 struct MoreInfo {
@@ -39,3 +39,56 @@ fn test_payload_length() {
         _ => panic!("expected `MessageTooLarge` error"),
     }
 }
+
+#[test]
+fn test_read_input_with_limit_rejects_oversized_header_before_allocating() {
+    let declared_len: u32 = 5 * 1024 * 1024;
+    let limit: u32 = 1024 * 1024;
+
+    // Only the 4-byte length header, no body: if the guard didn't run
+    // before allocating and reading the body, this would instead fail
+    // with an `Io`/unexpected-eof error.
+    let frame = declared_len.to_ne_bytes();
+
+    match read_input_with_limit(Cursor::new(&frame), limit)
+        .err()
+        .expect("expected error")
+    {
+        Error::MessageTooLargeRead {
+            size,
+            limit: got_limit,
+        } => {
+            assert_eq!(size, declared_len as usize);
+            assert_eq!(got_limit, limit as usize);
+        }
+        _ => panic!("expected `MessageTooLargeRead` error"),
+    }
+}
+
+#[test]
+fn test_oversized_message_does_not_desync_the_next_read() {
+    let limit: u32 = 16;
+
+    // An oversized frame whose body is actually present in the stream,
+    // followed by a second, valid frame within the limit.
+    let mut stream = Vec::new();
+    let oversized_body = " ".repeat(64);
+    stream.extend_from_slice(&(oversized_body.len() as u32).to_ne_bytes());
+    stream.extend_from_slice(oversized_body.as_bytes());
+    send_message(&mut stream, &json!({ "ok": true })).unwrap();
+
+    let mut reader = Cursor::new(stream);
+
+    match read_input_with_limit(&mut reader, limit)
+        .err()
+        .expect("expected error")
+    {
+        Error::MessageTooLargeRead { .. } => {}
+        _ => panic!("expected `MessageTooLargeRead` error"),
+    }
+
+    // The oversized body must have been drained, so this reads the
+    // second frame, not leftover bytes from the first.
+    let recovered: Value = read_input_with_limit(&mut reader, limit).unwrap();
+    assert_eq!(recovered, json!({ "ok": true }));
+}
@@ -0,0 +1,36 @@
+use chrome_native_messaging::{read_message, send_message, Error};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Greeting {
+    name: String,
+    times: u32,
+}
+
+#[test]
+fn test_read_message_deserializes_directly_into_t() {
+    let sent = Greeting {
+        name: "World".to_string(),
+        times: 3,
+    };
+
+    let mut buf = Vec::new();
+    send_message(&mut buf, &sent).unwrap();
+
+    let received: Greeting = read_message(buf.as_slice()).unwrap();
+    assert_eq!(received, sent);
+}
+
+#[test]
+fn test_read_message_reports_serde_error_on_type_mismatch() {
+    let mut buf = Vec::new();
+    send_message(&mut buf, &"just a string").unwrap();
+
+    match read_message::<Greeting, _>(buf.as_slice())
+        .err()
+        .expect("expected error")
+    {
+        Error::Serde(_) => {}
+        _ => panic!("expected `Serde` error"),
+    }
+}